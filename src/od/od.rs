@@ -12,11 +12,14 @@
 extern crate getopts;
 extern crate unindent;
 extern crate byteorder;
+extern crate toml;
+extern crate atty;
 
 mod multifilereader;
 mod prn_int;
 mod prn_char;
 mod prn_float;
+mod recordfmt;
 
 use std::f64;
 use unindent::*;
@@ -41,6 +44,15 @@ static VERSION: &'static str = env!("CARGO_PKG_VERSION");
 #[derive(Debug)]
 enum Radix { Decimal, Hexadecimal, Octal, Binary }
 
+#[derive(Debug, Clone, Copy)]
+enum Endian { Little, Big }
+
+#[derive(Debug, Clone, Copy)]
+enum ColorMode { Auto, Always, Never }
+
+#[derive(Debug, Clone, Copy)]
+enum ArrayMode { C, Rust }
+
 pub fn uumain(args: Vec<String>) -> i32 {
     let mut opts = getopts::Options::new();
 
@@ -72,9 +84,26 @@ pub fn uumain(args: Vec<String>) -> i32 {
     opts.optflag("F", "", "floating point double precision (64-bit) units");
 
     opts.optopt("t", "format", "select output format or formats", "TYPE");
+    opts.optopt("", "endian",
+                "byte order to assume for multi-byte units (default: little)",
+                "little|big");
+    opts.optopt("", "record-format",
+                "decode input as repeated records of the named format, as \
+                 loaded from --format-file", "NAME");
+    opts.optopt("", "format-file",
+                "TOML file of record formats, used with --record-format", "FILE");
+    opts.optopt("", "color",
+                "colorize output by byte category: auto (default), always or never",
+                "auto|always|never");
+    opts.optopt("", "array",
+                "emit the input as a source-code array literal instead of an \
+                 offset+columns dump", "c|rust");
+    opts.optopt("", "array-width",
+                "elements per line in --array output. 12 is assumed when not specified",
+                "N");
     opts.optflag("v", "output-duplicates", "do not use * to mark line suppression");
     opts.optopt("w", "width",
-                ("output BYTES bytes per output line. 32 is implied when BYTES is not \
+                ("output BYTES bytes per output line. 16 is implied when BYTES is not \
                  specified."),
                 "BYTES");
     opts.optflag("h", "help", "display this help and exit.");
@@ -104,31 +133,124 @@ pub fn uumain(args: Vec<String>) -> i32 {
         Err(f) => { panic!("Invalid -A/--address-radix\n{}", f) }
     };
 
-    // Gather up file names - args which don't start with '-'
-    let stdnionly = [InputSource::Stdin];
-    let inputs = args[1..]
-        .iter()
-        .filter_map(|w| match w as &str {
-            "--" => Some(InputSource::Stdin),
-            o if o.starts_with("-") => None,
-            x => Some(InputSource::FileName(x)),
-        })
-        .collect::<Vec<_>>();
-    // If no input files named, use stdin.
-    let inputs = if inputs.len() == 0 {
-        &stdnionly[..]
-    } else {
-        &inputs[..]
+    let endian = match parse_endian(matches.opt_str("endian")) {
+        Ok(e) => e,
+        Err(f) => { panic!("Invalid --endian\n{}", f) }
+    };
+
+    let color_mode = match parse_color_mode(matches.opt_str("color")) {
+        Ok(c) => c,
+        Err(f) => { panic!("Invalid --color\n{}", f) }
+    };
+    let colorize = match color_mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => atty::is(atty::Stream::Stdout),
     };
-    // Gather up format flags, we don't use getopts becase we need keep them in order.
-    let flags = args[1..]
-        .iter()
-        .filter_map(|w| match w as &str {
-            "--" => None,
-            o if o.starts_with("-") => Some(&o[1..]),
-            _ => None,
-        })
-        .collect::<Vec<_>>();
+
+    let linebytes = match matches.opt_str("w") {
+        None => 16,
+        Some(s) => match parse_byte_count(&s) {
+            Ok(v) => v,
+            Err(e) => panic!("Invalid -w/--width\n{}", e),
+        },
+    };
+    let skip_bytes = match matches.opt_str("j") {
+        None => 0,
+        Some(s) => match parse_byte_count(&s) {
+            Ok(v) => v,
+            Err(e) => panic!("Invalid -j/--skip-bytes\n{}", e),
+        },
+    };
+    let read_limit = match matches.opt_str("N") {
+        None => None,
+        Some(s) => match parse_byte_count(&s) {
+            Ok(v) => Some(v),
+            Err(e) => panic!("Invalid -N/--read-bytes\n{}", e),
+        },
+    };
+
+    // Options which consume the following argument as a value rather than a
+    // file name (getopts knows this, but we also walk the raw args below to
+    // keep format flags in the order the user gave them).
+    let value_opts = ["A", "j", "N", "S", "t", "w", "endian", "format", "record-format",
+                       "format-file", "color", "array", "array-width"];
+
+    // Gather up file names - args which don't start with '-' and which
+    // aren't the value belonging to one of value_opts.
+    let mut inputs = Vec::new();
+    let mut flags = Vec::new();
+    let mut skip_next = false;
+    for w in args[1..].iter() {
+        let w = w as &str;
+        if skip_next {
+            skip_next = false;
+            continue;
+        }
+        match w {
+            "--" => inputs.push(InputSource::Stdin),
+            o if o.starts_with("--") && o.len() > 2 => {
+                // Long option, optionally with an attached `--name=value`.
+                let rest = &o[2..];
+                match rest.find('=') {
+                    Some(i) => flags.push(&rest[0..i]),
+                    None => {
+                        if value_opts.contains(&rest) {
+                            skip_next = true;
+                        }
+                        flags.push(rest);
+                    }
+                }
+            }
+            o if o.starts_with("-") && o.len() > 1 => {
+                // Short option, optionally with an attached value (-tx1).
+                let rest = &o[1..];
+                let flag = &rest[0..1];
+                if value_opts.contains(&flag) && rest.len() == 1 {
+                    skip_next = true;
+                }
+                flags.push(flag);
+            }
+            x => inputs.push(InputSource::FileName(x)),
+        }
+    }
+    // If no input files named, use stdin.
+    if inputs.is_empty() {
+        inputs.push(InputSource::Stdin);
+    }
+    let inputs = &inputs[..];
+
+    if let Some(name) = matches.opt_str("record-format") {
+        let path = match matches.opt_str("format-file") {
+            Some(p) => p,
+            None => panic!("--record-format requires --format-file\n"),
+        };
+        let fmt = match recordfmt::load_format_file(&path, &name) {
+            Ok(f) => f,
+            Err(e) => panic!("{}", e),
+        };
+        return recordfmt::record_dump(inputs, &fmt);
+    }
+
+    if let Some(mode_str) = matches.opt_str("array") {
+        let mode = match mode_str.as_str() {
+            "c" => ArrayMode::C,
+            "rust" => ArrayMode::Rust,
+            _ => panic!("Invalid --array\nArray mode must be one of [c, rust]\n"),
+        };
+        let width = match matches.opt_str("array-width") {
+            None => 12,
+            Some(s) => s.parse().unwrap_or_else(|_| panic!("Invalid --array-width: {}\n", s)),
+        };
+        if width == 0 {
+            panic!("Invalid --array-width\nwidth must be greater than 0\n");
+        }
+        return array_dump(inputs, mode, width);
+    }
+
+    // flags now holds format flags (and the "t" marker for each -t use) in
+    // the order the user gave them.
+    let t_values = matches.opt_strs("t");
 
         // At the moment, char (-a & -c)formats need the driver to set up a
         // line by inserting a different # of of spaces at the start.
@@ -161,15 +283,16 @@ pub fn uumain(args: Vec<String>) -> i32 {
             writer: FormatWriter::FloatWriter(print_item_flo64), offmarg: 0
         };
 
-        fn mkfmt(itembytes: usize, fmtspec: &OdFormater) -> OdFormat {
+        fn mkfmt(itembytes: usize, fmtspec: &OdFormater, endian: Endian) -> OdFormat {
             OdFormat {
                 itembytes: itembytes,
                 writer: fmtspec.writer,
                 offmarg: fmtspec.offmarg,
+                print_ascii: false,
+                endian: endian,
             }
         }
 
-// TODO: -t fmts
         let known_formats = hashmap![
     		"a" => (1, &a_char),
     		"B" => (2, &oct) ,
@@ -194,115 +317,259 @@ pub fn uumain(args: Vec<String>) -> i32 {
     	];
 
         let mut formats = Vec::new();
+        let mut t_values = t_values.iter();
 
         for flag in flags.iter() {
+            if *flag == "t" || *flag == "format" {
+                // Each occurrence of -t/--format consumes the next parsed
+                // value in order; the type-spec grammar is handled by
+                // parse_format_spec.
+                if let Some(spec) = t_values.next() {
+                    formats.extend(parse_format_spec(spec, endian));
+                }
+                continue;
+            }
             match known_formats.get(flag) {
                 None => {} // not every option is a format
                 Some(r) => {
                     let (itembytes, fmtspec) = *r;
-                    formats.push(mkfmt(itembytes, fmtspec))
+                    formats.push(mkfmt(itembytes, fmtspec, endian))
                 }
             }
         }
 
         if formats.is_empty() {
-            formats.push(mkfmt(2, &oct)); // 2 byte octal is the default
+            formats.push(mkfmt(2, &oct, endian)); // 2 byte octal is the default
         }
 
-        odfunc(&input_offset_base, &inputs, &formats[..])
+        let print_duplicates = matches.opt_present("v");
+
+        let max_itembytes = formats.iter().map(|f| f.itembytes).max().unwrap_or(1);
+        if linebytes == 0 || linebytes % max_itembytes != 0 {
+            panic!("Invalid -w/--width\n{} is not a multiple of the largest format's item size ({})\n",
+                   linebytes, max_itembytes);
+        }
+
+        odfunc(&input_offset_base, inputs, &formats[..], colorize, print_duplicates, linebytes,
+               skip_bytes, read_limit)
+}
+
+// Distinct driver for --array: emits the whole input as a source-code array
+// literal instead of the offset+columns layout, so it skips print_with_radix
+// and the per-format offset margins entirely.
+fn array_dump(fnames: &[InputSource], mode: ArrayMode, width: usize) -> i32 {
+    let mut mf = MultifileReader::new(fnames);
+    let mut chunk = [0u8; 4096];
+    let mut data = Vec::new();
+    loop {
+        match mf.f_read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => data.extend_from_slice(&chunk[0..n]),
+            Err(_) => break,
+        }
+    }
+
+    match mode {
+        ArrayMode::C => println!("unsigned char data[] = {{"),
+        ArrayMode::Rust => println!("const DATA: [u8; {}] = [", data.len()),
+    }
+    for line in data.chunks(width) {
+        let items = line.iter()
+            .map(|b| format!("0x{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("    {},", items);
+    }
+    match mode {
+        ArrayMode::C => println!("}};"),
+        ArrayMode::Rust => println!("];"),
+    }
+
+    if mf.any_err {
+        1
+    } else {
+        0
+    }
 }
 
-const LINEBYTES:usize = 16;
 const WORDBYTES:usize = 2;
 
-fn odfunc(input_offset_base: &Radix, fnames: &[InputSource], formats: &[OdFormat]) -> i32 {
+// Reads into buf, bounded by the overall -N/--read-bytes limit (if any) and
+// how much of it total_read has already consumed.
+fn bounded_read(mf: &mut MultifileReader, buf: &mut [u8], read_limit: Option<usize>,
+                total_read: &mut usize) -> usize {
+    let want = match read_limit {
+        Some(limit) if *total_read >= limit => return 0,
+        Some(limit) => ::std::cmp::min(buf.len(), limit - *total_read),
+        None => buf.len(),
+    };
+    let n = match mf.f_read(&mut buf[0..want]) {
+        Ok(n) => n,
+        Err(_) => 0,
+    };
+    *total_read += n;
+    n
+}
+
+fn odfunc(input_offset_base: &Radix, fnames: &[InputSource], formats: &[OdFormat], colorize: bool,
+          print_duplicates: bool, linebytes: usize, skip_bytes: usize,
+          read_limit: Option<usize>) -> i32 {
 
     let mut mf = MultifileReader::new(fnames);
-    let mut addr = 0;
-    let bytes = &mut [b'\x00'; LINEBYTES];
+
+    // -j: discard the leading skip_bytes. This does not disturb offset
+    // accounting below since addr starts from skip_bytes rather than 0.
+    let mut to_skip = skip_bytes;
+    let mut discard = vec![0u8; linebytes];
+    while to_skip > 0 {
+        let want = ::std::cmp::min(to_skip, discard.len());
+        match mf.f_read(&mut discard[0..want]) {
+            Ok(0) => break,
+            Ok(n) => to_skip -= n,
+            Err(_) => break,
+        }
+    }
+
+    let mut addr = skip_bytes;
+    let mut total_read = 0;
+    let mut bytes = vec![0u8; linebytes];
+    let mut n = bounded_read(&mut mf, &mut bytes, read_limit, &mut total_read);
+    // One line of lookahead so the loop can detect end-of-input (n == 0) and
+    // print the terminating offset unconditionally, even when the last
+    // line(s) were collapsed into a "*".
+    let mut prev_line: Option<Vec<u8>> = None;
+    let mut suppressing = false;
+
     loop {
-        // print each line data (or multi-format raster of several lines describing the same data).
+        if n == 0 {
+            print_with_radix(input_offset_base, addr);
+            print!("\n");
+            break;
+        }
+
+        let mut next_bytes = vec![0u8; linebytes];
+        let next_n = bounded_read(&mut mf, &mut next_bytes, read_limit, &mut total_read);
+
+        let is_duplicate = !print_duplicates && n == linebytes &&
+            prev_line.as_ref().map_or(false, |p| p[..] == bytes[..]);
+
+        if is_duplicate {
+            if !suppressing {
+                println!("*");
+                suppressing = true;
+            }
+            prev_line = Some(bytes);
+            addr += n;
+            bytes = next_bytes;
+            n = next_n;
+            continue;
+        }
+        suppressing = false;
 
+        // print each line data (or multi-format raster of several lines describing the same data).
         print_with_radix(input_offset_base, addr); // print offset
 		// if printing in multiple formats offset is printed only once
 
-        match mf.f_read(bytes) {
-            Ok(0) => {
-                print!("\n");
-                break;
-            }
-            Ok(n) => {
-                let mut first = true; // First line of a multi-format raster.
-                for f in formats {
-                    if !first {
-                        // this takes the space of the file offset on subsequent
-                        // lines of multi-format rasters.
-                        print!("       ");
-                    }
-                    first = false;
-                    print!("{:>width$}", "", width = f.offmarg);// 4 spaces after offset - we print 2 more before each word
-
-                    // not enough byte for a whole element, this should only happen on the last line.
-                    if n % f.itembytes != 0 {
-                        let b = n / f.itembytes;
-                        // set zero bytes in the part of the buffer that will be used, but is not filled.
-                        for i in n..(b + 1) * f.itembytes {
-                            bytes[i] = 0;
-                        }
+        let mut first = true; // First line of a multi-format raster.
+        for f in formats {
+                if !first {
+                    // this takes the space of the file offset on subsequent
+                    // lines of multi-format rasters.
+                    print!("       ");
+                }
+                first = false;
+                print!("{:>width$}", "", width = f.offmarg);// 4 spaces after offset - we print 2 more before each word
+
+                // not enough byte for a whole element, this should only happen on the last line.
+                if n % f.itembytes != 0 {
+                    let b = n / f.itembytes;
+                    // set zero bytes in the part of the buffer that will be used, but is not filled.
+                    for i in n..(b + 1) * f.itembytes {
+                        bytes[i] = 0;
                     }
+                }
 
-                    let mut b = 0;
-                    while b < n {
-                        let nextb = b + f.itembytes;
-                        match f.writer {
-                            FormatWriter::IntWriter(func) => {
-                                let p: u64 = match f.itembytes {
-                                    1 => {
-                                        bytes[b] as u64
-                                    }
-                                    2 => {
-                                        LittleEndian::read_u16(&bytes[b..nextb]) as u64
-                                    }
-                                    4 => {
-                                        LittleEndian::read_u32(&bytes[b..nextb]) as u64
-                                    }
-                                    8 => {
-                                        LittleEndian::read_u64(&bytes[b..nextb])
-                                    }
-                                    _ => { panic!("Invalid itembytes: {}", f.itembytes); }
-                                };
+                let mut b = 0;
+                while b < n {
+                    let nextb = b + f.itembytes;
+                    match f.writer {
+                        FormatWriter::IntWriter(func) => {
+                            let p: u64 = match (f.itembytes, f.endian) {
+                                (1, _) => {
+                                    bytes[b] as u64
+                                }
+                                (2, Endian::Little) => {
+                                    LittleEndian::read_u16(&bytes[b..nextb]) as u64
+                                }
+                                (2, Endian::Big) => {
+                                    BigEndian::read_u16(&bytes[b..nextb]) as u64
+                                }
+                                (4, Endian::Little) => {
+                                    LittleEndian::read_u32(&bytes[b..nextb]) as u64
+                                }
+                                (4, Endian::Big) => {
+                                    BigEndian::read_u32(&bytes[b..nextb]) as u64
+                                }
+                                (8, Endian::Little) => {
+                                    LittleEndian::read_u64(&bytes[b..nextb])
+                                }
+                                (8, Endian::Big) => {
+                                    BigEndian::read_u64(&bytes[b..nextb])
+                                }
+                                _ => { panic!("Invalid itembytes: {}", f.itembytes); }
+                            };
+                            if colorize {
+                                print!("{}", byte_color(bytes[b]));
+                                func(p, f.itembytes);
+                                print!("{}", COLOR_RESET);
+                            } else {
                                 func(p, f.itembytes);
                             }
-                            FormatWriter::FloatWriter(func) => {
-                                let p: f64 = match f.itembytes {
-                                    4 => {
-                                        LittleEndian::read_f32(&bytes[b..nextb]) as f64
-                                    }
-                                    8 => {
-                                        LittleEndian::read_f64(&bytes[b..nextb])
-                                    }
-                                    _ => { panic!("Invalid itembytes: {}", f.itembytes); }
-                                };
+                        }
+                        FormatWriter::FloatWriter(func) => {
+                            let p: f64 = match (f.itembytes, f.endian) {
+                                (4, Endian::Little) => {
+                                    LittleEndian::read_f32(&bytes[b..nextb]) as f64
+                                }
+                                (4, Endian::Big) => {
+                                    BigEndian::read_f32(&bytes[b..nextb]) as f64
+                                }
+                                (8, Endian::Little) => {
+                                    LittleEndian::read_f64(&bytes[b..nextb])
+                                }
+                                (8, Endian::Big) => {
+                                    BigEndian::read_f64(&bytes[b..nextb])
+                                }
+                                _ => { panic!("Invalid itembytes: {}", f.itembytes); }
+                            };
+                            if colorize {
+                                print!("{}", byte_color(bytes[b]));
+                                func(p);
+                                print!("{}", COLOR_RESET);
+                            } else {
                                 func(p);
                             }
                         }
-                        b = nextb;
                     }
-                    // Add extra spaces to pad out the short, presumably last, line.
-                    if n < LINEBYTES {
-                        // calc # of items we did not print, must be short at least WORDBYTES to be missing any.
-                        let words_short = (LINEBYTES - n) / WORDBYTES;
-                        // XXX this is running short for -c & -a
-                        print!("{:>width$}", "", width = (words_short) * (6 + 2));
-                    }
-                    print!("\n");
+                    b = nextb;
                 }
-                addr += n;
-            }
-            Err(_) => {
-                break;
+                // Add extra spaces to pad out the short, presumably last, line.
+                if n < linebytes {
+                    // calc # of items we did not print, must be short at least WORDBYTES to be missing any.
+                    let words_short = (linebytes - n) / WORDBYTES;
+                    // XXX this is running short for -c & -a
+                    print!("{:>width$}", "", width = (words_short) * (6 + 2));
+                }
+                if f.print_ascii {
+                    print!("  {}", render_ascii_dump(&bytes[0..n]));
+                }
+                print!("\n");
             }
-        };
+
+        prev_line = Some(bytes);
+        addr += n;
+        bytes = next_bytes;
+        n = next_n;
     }
     if mf.any_err {
         1
@@ -334,6 +601,61 @@ fn parse_radix(radix_str: Option<String>) -> Result<Radix, &'static str> {
     }
 }
 
+// Renders bytes as printable ASCII for the 'z' modifier, substituting '.'
+// for anything outside the printable range.
+fn render_ascii_dump(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|&b| if b >= 0x20 && b < 0x7f { b as char } else { '.' })
+        .collect()
+}
+
+// For --endian.
+fn parse_endian(endian_str: Option<String>) -> Result<Endian, &'static str> {
+    match endian_str {
+        None => Ok(Endian::Little),
+        Some(ref s) if s == "little" => Ok(Endian::Little),
+        Some(ref s) if s == "big" => Ok(Endian::Big),
+        Some(_) => Err("Byte order must be one of [little, big]\n"),
+    }
+}
+
+// For --color.
+fn parse_color_mode(color_str: Option<String>) -> Result<ColorMode, &'static str> {
+    match color_str {
+        None => Ok(ColorMode::Auto),
+        Some(ref s) if s == "auto" => Ok(ColorMode::Auto),
+        Some(ref s) if s == "always" => Ok(ColorMode::Always),
+        Some(ref s) if s == "never" => Ok(ColorMode::Never),
+        Some(_) => Err("Color mode must be one of [auto, always, never]\n"),
+    }
+}
+
+const COLOR_RESET: &'static str = "\x1b[0m";
+
+// For -w/--width, -j/--skip-bytes and -N/--read-bytes: parses a decimal byte
+// count with an optional GNU od suffix multiplier (b=512, k=1024, m=1048576).
+fn parse_byte_count(s: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match s.chars().last() {
+        Some('b') => (&s[0..s.len() - 1], 512),
+        Some('k') => (&s[0..s.len() - 1], 1024),
+        Some('m') => (&s[0..s.len() - 1], 1024 * 1024),
+        _ => (s, 1),
+    };
+    let v: usize = digits.parse().map_err(|e| format!("{}\n", e))?;
+    v.checked_mul(multiplier).ok_or_else(|| format!("{} is too large\n", s))
+}
+
+// Picks an ANSI color for a byte based on its category, the way `hx` does:
+// NUL, printable ASCII, whitespace, and everything else (control/high bit).
+fn byte_color(b: u8) -> &'static str {
+    match b {
+        0x00 => "\x1b[90m",
+        0x09..=0x0d | 0x20 => "\x1b[36m",
+        0x21..=0x7e => "\x1b[32m",
+        _ => "\x1b[31m",
+    }
+}
+
 fn print_with_radix(r: &Radix, x: usize) {
     // TODO(keunwoo): field widths should be based on sizeof(x), or chosen dynamically based on the
     // expected range of address values.  Binary in particular is not great here.
@@ -355,4 +677,108 @@ struct OdFormat {
     itembytes: usize,
     writer: FormatWriter,
     offmarg: usize,
+    // Set by the trailing 'z' modifier on a -t/--format type spec: print the
+    // line's bytes as printable ASCII (or '.') after the numeric columns.
+    print_ascii: bool,
+    // Byte order to use when decoding multi-byte items, set by --endian.
+    endian: Endian,
+}
+
+// Parses one -t/--format argument into the OdFormat(s) it describes. The
+// grammar is GNU od's: a type letter (a, c, d, u, o, x, f), optionally
+// followed by a decimal byte count or a size letter (C/S/I/L for integer
+// types, F/D for f), with any number of (type, size) pairs concatenated.
+// A trailing 'z' enables the printable-ASCII dump for every format produced
+// by this argument.
+fn parse_format_spec(spec: &str, endian: Endian) -> Vec<OdFormat> {
+    let chars: Vec<char> = spec.chars().collect();
+    let mut i = 0;
+    let mut out = Vec::new();
+    let mut print_ascii = false;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == 'z' {
+            print_ascii = true;
+            i += 1;
+            continue;
+        }
+        i += 1;
+
+        let is_float = c == 'f';
+        let (default_bytes, offmarg) = match c {
+            'a' | 'c' => (1, 1),
+            'f' => (8, 0),
+            'd' | 'u' | 'o' | 'x' => (4, 2),
+            _ => panic!("Invalid -t/--format type spec: unknown type '{}'", c),
+        };
+
+        let mut itembytes = default_bytes;
+        if i < chars.len() && chars[i].is_digit(10) {
+            let start = i;
+            while i < chars.len() && chars[i].is_digit(10) {
+                i += 1;
+            }
+            itembytes = spec[start..i].parse().unwrap_or(default_bytes);
+        } else if i < chars.len() {
+            let mapped = if is_float {
+                match chars[i] {
+                    'F' => Some(4),
+                    'D' => Some(8),
+                    _ => None,
+                }
+            } else {
+                match chars[i] {
+                    'C' => Some(1),
+                    'S' => Some(2),
+                    'I' => Some(4),
+                    'L' => Some(8),
+                    _ => None,
+                }
+            };
+            if let Some(b) = mapped {
+                itembytes = b;
+                i += 1;
+            }
+        }
+
+        // 'a' and 'c' are always single bytes; any digits following them are
+        // a new type spec's size (unreachable here since only digits/size
+        // letters are consumed above, but keep itembytes honest).
+        if c == 'a' || c == 'c' {
+            itembytes = 1;
+        }
+
+        let writer = match c {
+            'a' => FormatWriter::IntWriter(print_item_a),
+            'c' => FormatWriter::IntWriter(print_item_c),
+            'd' => FormatWriter::IntWriter(print_item_dec_s),
+            'u' => FormatWriter::IntWriter(print_item_dec_u),
+            'o' => FormatWriter::IntWriter(print_item_oct),
+            'x' => FormatWriter::IntWriter(print_item_hex),
+            'f' => {
+                if itembytes == 4 {
+                    FormatWriter::FloatWriter(print_item_flo32)
+                } else {
+                    FormatWriter::FloatWriter(print_item_flo64)
+                }
+            }
+            _ => unreachable!(),
+        };
+
+        out.push(OdFormat {
+            itembytes: itembytes,
+            writer: writer,
+            offmarg: offmarg,
+            print_ascii: false,
+            endian: endian,
+        });
+    }
+
+    if print_ascii {
+        for f in out.iter_mut() {
+            f.print_ascii = true;
+        }
+    }
+    out
 }
@@ -0,0 +1,299 @@
+/*
+ * This file is part of the uutils coreutils package.
+ *
+ * For the full copyright and license information, please view the LICENSE
+ * file that was distributed with this source code.
+ */
+
+// Decodes a stream of fixed-layout binary records against a named format
+// loaded from a TOML file, printing each field's name, value and byte
+// offset. A format is a named, ordered list of fields; a field may itself
+// embed a previously-defined format by name, in which case that format's
+// fields are spliced in inline. Embeds are resolved only after the whole
+// file has been parsed, so formats may be declared in any order.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+
+use byteorder::*;
+use toml::Value;
+
+use super::Endian;
+use super::multifilereader::*;
+
+#[derive(Debug, Clone)]
+enum FieldType {
+    Int(usize),
+    Uint(usize),
+    Float,
+    Double,
+    Str(usize),
+    Bytes(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Field {
+    name: String,
+    ty: FieldType,
+    endian: Endian,
+    bit_flip: bool,
+}
+
+impl Field {
+    fn byte_len(&self) -> usize {
+        match self.ty {
+            FieldType::Int(bits) | FieldType::Uint(bits) => bits / 8,
+            FieldType::Float => 4,
+            FieldType::Double => 8,
+            FieldType::Str(n) | FieldType::Bytes(n) => n,
+        }
+    }
+
+    fn render(&self, raw: &[u8]) -> String {
+        let mut bytes = raw.to_vec();
+        if self.bit_flip {
+            for b in bytes.iter_mut() {
+                *b = !*b;
+            }
+        }
+        match self.ty {
+            FieldType::Uint(bits) => format!("{}", read_uint(&bytes, bits, self.endian)),
+            FieldType::Int(bits) => {
+                format!("{}", sign_extend(read_uint(&bytes, bits, self.endian), bits))
+            }
+            FieldType::Float => {
+                let v = match self.endian {
+                    Endian::Little => LittleEndian::read_f32(&bytes),
+                    Endian::Big => BigEndian::read_f32(&bytes),
+                };
+                format!("{}", v)
+            }
+            FieldType::Double => {
+                let v = match self.endian {
+                    Endian::Little => LittleEndian::read_f64(&bytes),
+                    Endian::Big => BigEndian::read_f64(&bytes),
+                };
+                format!("{}", v)
+            }
+            FieldType::Str(_) => {
+                String::from_utf8_lossy(&bytes).trim_end_matches('\x00').to_string()
+            }
+            FieldType::Bytes(_) => {
+                bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ")
+            }
+        }
+    }
+}
+
+fn read_uint(bytes: &[u8], bits: usize, endian: Endian) -> u64 {
+    match (bits, endian) {
+        (8, _) => bytes[0] as u64,
+        (16, Endian::Little) => LittleEndian::read_u16(bytes) as u64,
+        (16, Endian::Big) => BigEndian::read_u16(bytes) as u64,
+        (32, Endian::Little) => LittleEndian::read_u32(bytes) as u64,
+        (32, Endian::Big) => BigEndian::read_u32(bytes) as u64,
+        (64, Endian::Little) => LittleEndian::read_u64(bytes),
+        (64, Endian::Big) => BigEndian::read_u64(bytes),
+        _ => panic!("Unsupported integer width: {} bits", bits),
+    }
+}
+
+fn sign_extend(v: u64, bits: usize) -> i64 {
+    if bits >= 64 {
+        return v as i64;
+    }
+    let shift = 64 - bits;
+    ((v << shift) as i64) >> shift
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordFormat {
+    name: String,
+    fields: Vec<Field>,
+}
+
+impl RecordFormat {
+    fn byte_len(&self) -> usize {
+        self.fields.iter().map(Field::byte_len).sum()
+    }
+}
+
+// An unresolved field, as it comes straight out of the TOML before any
+// embedded formats have been spliced in.
+struct RawField {
+    name: String,
+    kind: String,
+    bits: Option<u64>,
+    len: Option<u64>,
+    endian: Option<String>,
+    bit_flip: bool,
+    embed: Option<String>,
+}
+
+struct RawFormat {
+    name: String,
+    fields: Vec<RawField>,
+}
+
+pub fn load_format_file(path: &str, want: &str) -> Result<RecordFormat, String> {
+    let mut contents = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("{}: {}", path, e))?;
+
+    let root = contents.parse::<Value>().map_err(|e| format!("{}: {}", path, e))?;
+    let format_list = root.get("format")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("{}: no [[format]] tables found", path))?;
+
+    let mut raw_formats = HashMap::new();
+    for tbl in format_list {
+        let raw = parse_raw_format(tbl, path)?;
+        raw_formats.insert(raw.name.clone(), raw);
+    }
+
+    let mut resolved = HashMap::new();
+    let mut in_progress = Vec::new();
+    resolve_format(want, &raw_formats, &mut resolved, &mut in_progress)?;
+
+    resolved.remove(want)
+        .ok_or_else(|| format!("format '{}' not found in {}", want, path))
+}
+
+fn parse_raw_format(tbl: &Value, path: &str) -> Result<RawFormat, String> {
+    let name = tbl.get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| format!("{}: a [[format]] table is missing 'name'", path))?
+        .to_string();
+
+    let field_list = tbl.get("field")
+        .and_then(Value::as_array)
+        .ok_or_else(|| format!("{}: format '{}' has no [[format.field]] entries", path, name))?;
+
+    let mut fields = Vec::new();
+    for f in field_list {
+        let fname = f.get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("{}: format '{}' has a field with no 'name'", path, name))?
+            .to_string();
+        let kind = f.get("type")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("{}: field '{}' in format '{}' has no 'type'", path, fname, name))?
+            .to_string();
+        fields.push(RawField {
+            name: fname,
+            kind: kind,
+            bits: f.get("bits").and_then(Value::as_integer).map(|v| v as u64),
+            len: f.get("len").and_then(Value::as_integer).map(|v| v as u64),
+            endian: f.get("endian").and_then(Value::as_str).map(|s| s.to_string()),
+            bit_flip: f.get("bit_flip").and_then(Value::as_bool).unwrap_or(false),
+            embed: f.get("format").and_then(Value::as_str).map(|s| s.to_string()),
+        });
+    }
+
+    Ok(RawFormat { name: name, fields: fields })
+}
+
+fn resolve_format(
+    name: &str,
+    raw: &HashMap<String, RawFormat>,
+    out: &mut HashMap<String, RecordFormat>,
+    in_progress: &mut Vec<String>,
+) -> Result<(), String> {
+    if out.contains_key(name) {
+        return Ok(());
+    }
+    if in_progress.iter().any(|n| n == name) {
+        return Err(format!("format '{}' embeds itself", name));
+    }
+    let r = raw.get(name).ok_or_else(|| format!("unknown format '{}'", name))?;
+
+    in_progress.push(name.to_string());
+    let mut fields = Vec::new();
+    for rf in &r.fields {
+        if rf.kind == "format" {
+            let embed_name = rf.embed.as_ref()
+                .ok_or_else(|| format!("field '{}' of format '{}' is type 'format' but has no 'format' key", rf.name, name))?;
+            resolve_format(embed_name, raw, out, in_progress)?;
+            let embedded = out.get(embed_name).expect("just resolved above");
+            fields.extend(embedded.fields.iter().cloned());
+        } else {
+            fields.push(build_field(rf, name)?);
+        }
+    }
+    in_progress.pop();
+
+    out.insert(name.to_string(), RecordFormat { name: name.to_string(), fields: fields });
+    Ok(())
+}
+
+// read_uint only knows how to decode whole-byte widths of 8, 16, 32 or 64
+// bits, so reject anything else here rather than panicking mid-dump.
+fn valid_int_bits(bits: u64, field_name: &str, format_name: &str) -> Result<usize, String> {
+    match bits {
+        8 | 16 | 32 | 64 => Ok(bits as usize),
+        other => Err(format!(
+            "field '{}' of format '{}' has unsupported bit width {} (must be 8, 16, 32 or 64)",
+            field_name, format_name, other)),
+    }
+}
+
+fn build_field(rf: &RawField, format_name: &str) -> Result<Field, String> {
+    let endian = match rf.endian.as_ref().map(|s| s.as_str()) {
+        None | Some("little") => Endian::Little,
+        Some("big") => Endian::Big,
+        Some(other) => return Err(format!(
+            "field '{}' of format '{}' has unknown endian '{}'", rf.name, format_name, other)),
+    };
+
+    let ty = match rf.kind.as_str() {
+        "int" => FieldType::Int(valid_int_bits(rf.bits.unwrap_or(32), &rf.name, format_name)?),
+        "uint" => FieldType::Uint(valid_int_bits(rf.bits.unwrap_or(32), &rf.name, format_name)?),
+        "float" => FieldType::Float,
+        "double" => FieldType::Double,
+        "string" => FieldType::Str(rf.len.ok_or_else(|| format!(
+            "field '{}' of format '{}' is type 'string' but has no 'len'", rf.name, format_name))? as usize),
+        "bytes" => FieldType::Bytes(rf.len.ok_or_else(|| format!(
+            "field '{}' of format '{}' is type 'bytes' but has no 'len'", rf.name, format_name))? as usize),
+        other => return Err(format!(
+            "field '{}' of format '{}' has unknown type '{}'", rf.name, format_name, other)),
+    };
+
+    Ok(Field { name: rf.name.clone(), ty: ty, endian: endian, bit_flip: rf.bit_flip })
+}
+
+pub fn record_dump(fnames: &[InputSource], fmt: &RecordFormat) -> i32 {
+    let mut mf = MultifileReader::new(fnames);
+    let reclen = fmt.byte_len();
+    let mut buf = vec![0u8; reclen];
+    let mut addr = 0usize;
+
+    loop {
+        match mf.f_read(&mut buf[..]) {
+            Ok(0) => break,
+            Ok(n) if n < reclen => {
+                println!("{:07o} <truncated record: {} of {} bytes read>", addr, n, reclen);
+                addr += n;
+                break;
+            }
+            Ok(n) => {
+                println!("{:07o} {}:", addr, fmt.name);
+                let mut off = 0;
+                for field in &fmt.fields {
+                    let flen = field.byte_len();
+                    println!("  +{:<6} {:<16} {}", off, field.name, field.render(&buf[off..off + flen]));
+                    off += flen;
+                }
+                addr += n;
+            }
+            Err(_) => break,
+        }
+    }
+
+    if mf.any_err {
+        1
+    } else {
+        0
+    }
+}